@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::Object;
+
+/// Digest of a file's raw bytes, used to decide whether a SNO JSON file needs
+/// to be re-parsed or can be served from the cache as-is.
+pub type FileHash = [u8; 32];
+
+/// The subset of [`Object`] that's worth persisting across runs. The
+/// traversal-only fields (`incoming_filtered`, `outgoing_filtered`,
+/// `distances`, `collapsed`) are derived fresh on every invocation from the CLI args, so
+/// they're deliberately left out here.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedObject {
+    pub filename: String,
+    pub id: usize,
+    pub outbound_references: Vec<usize>,
+}
+
+impl From<&Object> for ParsedObject {
+    fn from(object: &Object) -> Self {
+        ParsedObject {
+            filename: object.filename.clone(),
+            id: object.id,
+            outbound_references: object.outbound_references.clone(),
+        }
+    }
+}
+
+/// One entry in the cache manifest: the hash of the source file's bytes at
+/// the time it was parsed, and the parsed object that resulted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub hash: FileHash,
+    pub object: ParsedObject,
+}
+
+/// On-disk cache mapping a SNO JSON file's path to the hash it was parsed
+/// from and the resulting [`ParsedObject`]. Loaded wholesale and diffed
+/// against the current file list so unchanged files never need to touch the
+/// JSON parser again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+impl Cache {
+    /// Loads a cache written by [`Cache::to_writer`], or an empty cache if
+    /// `path` doesn't exist yet (e.g. first run).
+    pub fn from_path(path: &Path) -> Self {
+        match File::open(path) {
+            Ok(file) => Self::from_reader(BufReader::new(file)).unwrap_or_default(),
+            Err(_) => Cache::default(),
+        }
+    }
+
+    fn from_reader<R: std::io::Read>(reader: R) -> bincode::Result<Self> {
+        bincode::deserialize_from(reader)
+    }
+
+    /// Serializes the cache to `path`, overwriting whatever was there.
+    pub fn to_path(&self, path: &Path) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        self.to_writer(BufWriter::new(file))
+            .map_err(std::io::Error::other)
+    }
+
+    fn to_writer<W: std::io::Write>(&self, writer: W) -> bincode::Result<()> {
+        bincode::serialize_into(writer, self)
+    }
+
+    /// Returns the cached object for `path` if its on-disk hash still matches
+    /// `hash`, i.e. the file hasn't changed since it was last parsed.
+    pub fn lookup(&self, path: &Path, hash: &FileHash) -> Option<&ParsedObject> {
+        self.entries
+            .get(path)
+            .filter(|entry| &entry.hash == hash)
+            .map(|entry| &entry.object)
+    }
+
+    /// Records (or replaces) the parsed object for `path`.
+    pub fn insert(&mut self, path: PathBuf, hash: FileHash, object: ParsedObject) {
+        self.entries.insert(path, CacheEntry { hash, object });
+    }
+}
+
+/// Hashes a file's raw bytes with SHA3-256 so the cache can detect changes
+/// without relying on filesystem mtimes (which don't survive a fresh
+/// checkout or rsync).
+pub fn hash_bytes(bytes: &[u8]) -> FileHash {
+    let mut hasher = Sha3_256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
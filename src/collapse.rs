@@ -0,0 +1,191 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use petgraph::prelude::*;
+
+use crate::Object;
+
+impl Object {
+    /// Builds the condensed placeholder node that replaces a collapsed
+    /// linear chain, carrying enough of the run's endpoints to be
+    /// identifiable in the rendered graph.
+    fn condensed(run: &[NodeIndex], graph: &DiGraph<Object, i32>) -> Object {
+        let start = &graph[run[0]];
+        let end = &graph[*run.last().unwrap()];
+
+        Object {
+            filename: format!(
+                "{} .. {}",
+                start.filename.split('/').last().unwrap(),
+                end.filename.split('/').last().unwrap()
+            ),
+            id: start.id,
+            incoming_filtered: AtomicBool::new(start.incoming_filtered.load(Ordering::Relaxed)),
+            outgoing_filtered: AtomicBool::new(end.outgoing_filtered.load(Ordering::Relaxed)),
+            distances: Mutex::new(start.distances.lock().unwrap().clone()),
+            outbound_references: Vec::new(),
+            collapsed: Some(CollapsedRun {
+                count: run.len(),
+                start_id: start.id,
+                end_id: end.id,
+            }),
+        }
+    }
+
+    fn copy_of(other: &Object) -> Object {
+        Object {
+            filename: other.filename.clone(),
+            id: other.id,
+            incoming_filtered: AtomicBool::new(other.incoming_filtered.load(Ordering::Relaxed)),
+            outgoing_filtered: AtomicBool::new(other.outgoing_filtered.load(Ordering::Relaxed)),
+            distances: Mutex::new(other.distances.lock().unwrap().clone()),
+            outbound_references: other.outbound_references.clone(),
+            collapsed: other.collapsed.clone(),
+        }
+    }
+}
+
+/// Summary of a linear reference chain that was collapsed into a single
+/// placeholder node, so its `Display` can still say something useful about
+/// what it stands for.
+#[derive(Debug, Clone)]
+pub struct CollapsedRun {
+    pub count: usize,
+    pub start_id: usize,
+    pub end_id: usize,
+}
+
+fn out_degree(graph: &DiGraph<Object, i32>, node: NodeIndex) -> usize {
+    graph.edges_directed(node, Direction::Outgoing).count()
+}
+
+fn in_degree(graph: &DiGraph<Object, i32>, node: NodeIndex) -> usize {
+    graph.edges_directed(node, Direction::Incoming).count()
+}
+
+/// The run's only successor, if the run can be safely extended through it:
+/// `node` must have exactly one outgoing edge, and that edge's target must
+/// have exactly one incoming edge and satisfy `eligible`.
+fn single_chain_successor(
+    graph: &DiGraph<Object, i32>,
+    node: NodeIndex,
+    eligible: &impl Fn(NodeIndex) -> bool,
+) -> Option<NodeIndex> {
+    if out_degree(graph, node) != 1 {
+        return None;
+    }
+
+    let successor = graph
+        .edges_directed(node, Direction::Outgoing)
+        .next()?
+        .target();
+
+    (in_degree(graph, successor) == 1 && eligible(successor)).then_some(successor)
+}
+
+/// True if `node` is a mid-chain node, i.e. some other eligible node would
+/// already extend its run forward into `node`. Runs are only discovered from
+/// their head so each one is collapsed exactly once.
+fn is_mid_chain(
+    graph: &DiGraph<Object, i32>,
+    node: NodeIndex,
+    eligible: &impl Fn(NodeIndex) -> bool,
+) -> bool {
+    if in_degree(graph, node) != 1 {
+        return false;
+    }
+
+    let Some(predecessor) = graph
+        .edges_directed(node, Direction::Incoming)
+        .next()
+        .map(|edge| edge.source())
+    else {
+        return false;
+    };
+
+    eligible(predecessor) && out_degree(graph, predecessor) == 1
+}
+
+/// Finds every maximal run of pass-through nodes in `graph` — nodes where
+/// out-degree and the successor's in-degree are both 1 and both satisfy
+/// `eligible` — and replaces each run with a single condensed node. Returns
+/// the rebuilt graph and the number of runs collapsed.
+///
+/// `eligible` lets the caller exclude nodes that shouldn't be swallowed into
+/// a run, e.g. the query's target node or nodes already part of a reference
+/// cycle.
+pub fn collapse_chains(
+    graph: &DiGraph<Object, i32>,
+    eligible: impl Fn(NodeIndex) -> bool,
+) -> (DiGraph<Object, i32>, usize) {
+    let mut visited = HashSet::new();
+    let mut runs = Vec::new();
+
+    for start in graph.node_indices() {
+        if visited.contains(&start) || !eligible(start) || is_mid_chain(graph, start, &eligible) {
+            continue;
+        }
+
+        let mut run = vec![start];
+        visited.insert(start);
+        let mut current = start;
+
+        while let Some(next) = single_chain_successor(graph, current, &eligible) {
+            // A cycle closing back on the run's own head would otherwise
+            // loop forever.
+            if visited.contains(&next) {
+                break;
+            }
+            run.push(next);
+            visited.insert(next);
+            current = next;
+        }
+
+        if run.len() > 1 {
+            runs.push(run);
+        }
+    }
+
+    let collapsed_count = runs.len();
+
+    // Every node in `graph` maps to exactly one node in the rebuilt graph:
+    // either itself (untouched) or the condensed node standing in for its run.
+    let mut new_graph = DiGraph::new();
+    let mut node_mapping = HashMap::new();
+
+    let run_members: HashSet<NodeIndex> = runs.iter().flatten().copied().collect();
+
+    for run in &runs {
+        let condensed = new_graph.add_node(Object::condensed(run, graph));
+        for &member in run {
+            node_mapping.insert(member, condensed);
+        }
+    }
+
+    for node in graph.node_indices() {
+        if !run_members.contains(&node) {
+            let new_node = new_graph.add_node(Object::copy_of(&graph[node]));
+            node_mapping.insert(node, new_node);
+        }
+    }
+
+    let mut edges = HashSet::new();
+    for edge in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(edge).unwrap();
+        let new_source = node_mapping[&source];
+        let new_target = node_mapping[&target];
+        if new_source != new_target {
+            edges.insert((new_source, new_target));
+        }
+    }
+    for (source, target) in edges {
+        new_graph.add_edge(source, target, 1);
+    }
+
+    (new_graph, collapsed_count)
+}
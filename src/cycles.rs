@@ -0,0 +1,41 @@
+use std::collections::HashSet;
+
+use petgraph::{algo::tarjan_scc, prelude::*};
+
+use crate::Object;
+
+/// A strongly-connected component with more than one member, i.e. a set of
+/// SNO objects that mutually reference each other (directly or through
+/// intermediates).
+pub struct Cycle {
+    pub nodes: Vec<NodeIndex>,
+    /// The edges whose source and target both lie within this component —
+    /// the references that actually close the loop(s).
+    pub edges: Vec<(NodeIndex, NodeIndex)>,
+}
+
+/// Runs Tarjan's SCC algorithm over `graph` and returns every component with
+/// more than one node, each paired with the edges that close its loop(s).
+/// Single-node components (the overwhelming majority of a reference graph)
+/// are not cycles and are skipped.
+pub fn find_cycles(graph: &DiGraph<Object, i32>) -> Vec<Cycle> {
+    tarjan_scc(graph)
+        .into_iter()
+        .filter(|scc| scc.len() > 1)
+        .map(|nodes| {
+            let members: HashSet<NodeIndex> = nodes.iter().copied().collect();
+            let edges = nodes
+                .iter()
+                .flat_map(|&node| {
+                    graph
+                        .edges_directed(node, Direction::Outgoing)
+                        .filter(|edge| members.contains(&edge.target()))
+                        .map(|edge| (edge.source(), edge.target()))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+
+            Cycle { nodes, edges }
+        })
+        .collect()
+}
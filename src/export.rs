@@ -0,0 +1,222 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    sync::atomic::Ordering,
+};
+
+use petgraph::{
+    dot::{Config, Dot},
+    prelude::*,
+    visit::EdgeRef,
+};
+use serde::Serialize;
+
+use crate::Object;
+
+/// Graph output format, selected via `--format`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    Dot,
+    Graphml,
+    Json,
+}
+
+/// Serializes the rendered neighborhood graph to a writer. Implemented once
+/// per `--format` so `main` doesn't need to know the details of any one
+/// output shape.
+pub trait Exporter {
+    fn export(&self, graph: &DiGraph<Object, i32>, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Graphviz DOT output (the tool's original and default format). The caller
+/// precomputes each node's fill style (target coloring, shared-dependency
+/// shading, cycle highlighting, ...) so the exporter only has to render it.
+pub struct DotExporter {
+    pub node_styles: HashMap<NodeIndex, String>,
+    pub cycle_edges: HashSet<(NodeIndex, NodeIndex)>,
+}
+
+impl Exporter for DotExporter {
+    fn export(&self, graph: &DiGraph<Object, i32>, writer: &mut dyn Write) -> io::Result<()> {
+        // Bound to locals (rather than passed inline) so they outlive the
+        // `Dot` value below, which borrows them for the duration of its own
+        // `Display` impl.
+        let edge_getter = |_graph, edge_ref| {
+            if self
+                .cycle_edges
+                .contains(&(edge_ref.source(), edge_ref.target()))
+            {
+                ["color=red", "style=bold"].join(" ")
+            } else {
+                "color=white".to_string()
+            }
+        };
+        let node_getter = |_graph, node_ref: (NodeIndex, &Object)| {
+            self.node_styles
+                .get(&node_ref.0)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let dot_data =
+            Dot::with_attr_getters(graph, &[Config::EdgeNoLabel], &edge_getter, &node_getter);
+
+        let header = r#"digraph {
+        bgcolor=black
+        node [color=white fillcolor=black style=filled fontcolor=white shape=box]
+        edge [color=white]
+    "#;
+        let digraph_str = "digraph {";
+
+        let mut generated = Vec::new();
+        write!(generated, "{}", dot_data)?;
+
+        writer.write_all(header.as_bytes())?;
+        writer.write_all(&generated[digraph_str.len()..])?;
+
+        Ok(())
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// GraphML output, for loading the neighborhood into general-purpose graph
+/// viewers/databases. Node metadata is exposed as typed `<data>` attributes
+/// rather than baked into a label string.
+pub struct GraphmlExporter;
+
+impl Exporter for GraphmlExporter {
+    fn export(&self, graph: &DiGraph<Object, i32>, writer: &mut dyn Write) -> io::Result<()> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="filename" for="node" attr.name="filename" attr.type="string"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="distance" for="node" attr.name="distance" attr.type="int"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="reach_count" for="node" attr.name="reach_count" attr.type="int"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="incoming_filtered" for="node" attr.name="incoming_filtered" attr.type="boolean"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="outgoing_filtered" for="node" attr.name="outgoing_filtered" attr.type="boolean"/>"#
+        )?;
+        writeln!(writer, r#"  <graph id="d4graph" edgedefault="directed">"#)?;
+
+        for node in graph.node_indices() {
+            let obj = &graph[node];
+            writeln!(writer, r#"    <node id="{}">"#, obj.id)?;
+            writeln!(
+                writer,
+                r#"      <data key="filename">{}</data>"#,
+                escape_xml(&obj.filename)
+            )?;
+            writeln!(
+                writer,
+                r#"      <data key="distance">{}</data>"#,
+                obj.min_distance().unwrap_or_default()
+            )?;
+            writeln!(
+                writer,
+                r#"      <data key="reach_count">{}</data>"#,
+                obj.reach_count()
+            )?;
+            writeln!(
+                writer,
+                r#"      <data key="incoming_filtered">{}</data>"#,
+                obj.incoming_filtered.load(Ordering::Relaxed)
+            )?;
+            writeln!(
+                writer,
+                r#"      <data key="outgoing_filtered">{}</data>"#,
+                obj.outgoing_filtered.load(Ordering::Relaxed)
+            )?;
+            writeln!(writer, r#"    </node>"#)?;
+        }
+
+        for edge in graph.edge_references() {
+            writeln!(
+                writer,
+                r#"    <edge source="{}" target="{}"/>"#,
+                graph[edge.source()].id,
+                graph[edge.target()].id
+            )?;
+        }
+
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct JsonNode {
+    id: usize,
+    filename: String,
+    distance: usize,
+    reach_count: usize,
+    incoming_filtered: bool,
+    outgoing_filtered: bool,
+}
+
+#[derive(Serialize)]
+struct JsonEdge {
+    source: usize,
+    target: usize,
+}
+
+#[derive(Serialize)]
+struct JsonGraph {
+    nodes: Vec<JsonNode>,
+    edges: Vec<JsonEdge>,
+}
+
+/// Node-link JSON output, the same shape networkx/d3 consume.
+pub struct JsonExporter;
+
+impl Exporter for JsonExporter {
+    fn export(&self, graph: &DiGraph<Object, i32>, writer: &mut dyn Write) -> io::Result<()> {
+        let nodes = graph
+            .node_indices()
+            .map(|node| {
+                let obj = &graph[node];
+                JsonNode {
+                    id: obj.id,
+                    filename: obj.filename.clone(),
+                    distance: obj.min_distance().unwrap_or_default(),
+                    reach_count: obj.reach_count(),
+                    incoming_filtered: obj.incoming_filtered.load(Ordering::Relaxed),
+                    outgoing_filtered: obj.outgoing_filtered.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+
+        let edges = graph
+            .edge_references()
+            .map(|edge| JsonEdge {
+                source: graph[edge.source()].id,
+                target: graph[edge.target()].id,
+            })
+            .collect();
+
+        serde_json::to_writer_pretty(writer, &JsonGraph { nodes, edges }).map_err(io::Error::other)
+    }
+}
@@ -1,27 +1,28 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashMap, HashSet},
     fmt::Display,
-    io::Write,
     path::PathBuf,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        Mutex,
     },
 };
 
-use clap::{command, Parser};
+use clap::Parser;
 use indicatif::ProgressBar;
-use petgraph::{
-    algo::dijkstra,
-    data::Build,
-    dot::{Config, Dot},
-    graph::Node,
-    prelude::*,
-    visit::{IntoEdgesDirected, IntoNodeIdentifiers},
-};
+use petgraph::{prelude::*, visit::EdgeRef};
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
+mod cache;
+mod collapse;
+mod cycles;
+mod export;
+mod pathfind;
+
+use cache::{Cache, ParsedObject};
+use export::{DotExporter, Exporter, Format, GraphmlExporter, JsonExporter};
+
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -34,9 +35,16 @@ struct Args {
     #[clap(long, default_value = "3")]
     outgoing_count: usize,
 
-    /// SNO ID to consider as our target node (defaults to SecretCellar.qst)
+    /// SNO ID to consider as a target node (defaults to SecretCellar.qst).
+    /// May be repeated to visualize several targets' neighborhoods together.
     #[clap(short, long, default_value = "1315204")]
-    target_node_id: usize,
+    target_node_id: Vec<usize>,
+
+    /// How to combine multiple --target-node-id neighborhoods: `union` shows
+    /// every target's neighborhood together, `intersect` keeps only nodes
+    /// that lie in every target's incoming/outgoing neighborhood
+    #[clap(long, value_enum, default_value = "union")]
+    mode: NeighborhoodMode,
 
     /// Filter out incoming/outgoing nodes if there are more than this count
     #[clap(long, default_value = "20")]
@@ -45,25 +53,117 @@ struct Args {
     #[clap(short, long, default_value = "graph.dot")]
     out_file: PathBuf,
 
+    /// Path to the incremental parse cache. Unchanged files (by content hash)
+    /// are loaded from here instead of being re-parsed.
+    #[clap(long, default_value = "d4graph-cache.bin")]
+    cache_file: PathBuf,
+
+    /// Instead of emitting the neighborhood graph, find and print the
+    /// shortest reference path from the first `target_node_id` to this SNO
+    /// ID. Only a
+    /// single shortest path is returned, not the k shortest paths or their
+    /// induced subgraph.
+    #[clap(long)]
+    path_to_node_id: Option<usize>,
+
+    /// Use a landmark-based A* heuristic for the path query instead of plain
+    /// Dijkstra (only relevant with --path-to-node-id)
+    #[clap(long, requires = "path_to_node_id")]
+    use_landmarks: bool,
+
+    /// Number of high-degree landmark nodes to precompute for --use-landmarks
+    #[clap(long, default_value = "8")]
+    landmark_count: usize,
+
+    /// Instead of emitting the neighborhood graph, scan the full reference
+    /// graph for cycles (SCCs with more than one node) and report them
+    #[clap(long)]
+    detect_cycles: bool,
+
+    /// Collapse maximal linear "pass-through" chains in the rendered
+    /// neighborhood into a single condensed node each
+    #[clap(long)]
+    collapse_chains: bool,
+
+    /// Output format for the rendered neighborhood graph
+    #[clap(long, value_enum, default_value = "dot")]
+    format: Format,
+
     /// Number of times to greet
     json_path: PathBuf,
 }
 
+/// How neighborhoods from multiple `--target-node-id` values are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum NeighborhoodMode {
+    Union,
+    Intersect,
+}
+
 #[derive(Debug)]
 struct Object {
-    filename: String,
-    id: usize,
-    incoming_filtered: AtomicBool,
-    outgoing_filtered: AtomicBool,
-    distance: AtomicUsize,
-    outbound_references: Vec<usize>,
+    pub(crate) filename: String,
+    pub(crate) id: usize,
+    pub(crate) incoming_filtered: AtomicBool,
+    pub(crate) outgoing_filtered: AtomicBool,
+    /// Distance from each target SNO ID that reached this node during its
+    /// BFS, keyed by the target's SNO ID. A node with more than one entry is
+    /// a shared dependency of multiple targets.
+    pub(crate) distances: Mutex<HashMap<usize, usize>>,
+    pub(crate) outbound_references: Vec<usize>,
+    /// Set when this node stands in for a collapsed linear reference chain
+    /// (see `--collapse-chains`).
+    pub(crate) collapsed: Option<collapse::CollapsedRun>,
+}
+
+impl Object {
+    /// Reconstructs an `Object` from its persisted form, initializing the
+    /// traversal-only fields fresh since they only make sense for the
+    /// current run's args and BFS state.
+    fn from_parsed(parsed: ParsedObject) -> Self {
+        Object {
+            filename: parsed.filename,
+            id: parsed.id,
+            outbound_references: parsed.outbound_references,
+            incoming_filtered: Default::default(),
+            outgoing_filtered: Default::default(),
+            distances: Default::default(),
+            collapsed: None,
+        }
+    }
+
+    /// The smallest distance recorded from any target that reached this
+    /// node, or `None` if no target's BFS visited it.
+    pub(crate) fn min_distance(&self) -> Option<usize> {
+        self.distances.lock().unwrap().values().copied().min()
+    }
+
+    /// How many distinct targets reached this node.
+    pub(crate) fn reach_count(&self) -> usize {
+        self.distances.lock().unwrap().len()
+    }
 }
 
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{}", self.filename.split("/").last().unwrap())?;
         writeln!(f, "sno={}", self.id)?;
-        writeln!(f, "distance={}", self.distance.load(Ordering::Relaxed))?;
+
+        let distances = self.distances.lock().unwrap();
+        let mut target_ids: Vec<_> = distances.keys().copied().collect();
+        target_ids.sort_unstable();
+        for target_id in target_ids {
+            writeln!(f, "distance[{}]={}", target_id, distances[&target_id])?;
+        }
+        drop(distances);
+
+        if let Some(collapsed) = &self.collapsed {
+            writeln!(
+                f,
+                "{} nodes collapsed ({} .. {})",
+                collapsed.count, collapsed.start_id, collapsed.end_id
+            )?;
+        }
 
         if self.incoming_filtered.load(Ordering::Relaxed) {
             writeln!(f, "(incoming edges not shown)")?;
@@ -101,24 +201,28 @@ fn main() {
 
     let mut graph = DiGraph::new();
 
+    println!("Loading cache");
+    let old_cache = Cache::from_path(&args.cache_file);
+
     println!("Building objects");
-    let mut pb = ProgressBar::new(files.len() as u64);
-    let objects: Vec<_> = files
+    let pb = ProgressBar::new(files.len() as u64);
+    let parsed_files: Vec<(PathBuf, cache::FileHash, ParsedObject)> = files
         .par_iter()
         .filter_map(|file| {
             pb.inc(1);
             let contents = std::fs::read(file).expect("failed to read file");
+            let hash = cache::hash_bytes(&contents);
+
+            if let Some(cached) = old_cache.lookup(file, &hash) {
+                return Some((file.clone(), hash, cached.clone()));
+            }
+
             let json_obj: serde_json::Value =
                 serde_json::from_slice(contents.as_slice()).expect("failed to parse JSON");
 
-            let mut return_object = Object {
-                filename: json_obj["__fileName__"].as_str()?.to_string(),
-                id: json_obj["__snoID__"].as_u64()? as usize,
-                outbound_references: Vec::new(),
-                incoming_filtered: Default::default(),
-                outgoing_filtered: Default::default(),
-                distance: Default::default(),
-            };
+            let mut outbound_references = Vec::new();
+            let filename = json_obj["__fileName__"].as_str()?.to_string();
+            let id = json_obj["__snoID__"].as_u64()? as usize;
 
             let mut obj_queue = Vec::new();
             obj_queue.push(&json_obj);
@@ -127,9 +231,7 @@ fn main() {
                 if let Some(obj) = obj.as_object() {
                     if obj.contains_key("__raw__") && obj.contains_key("name") {
                         // reference to another file
-                        return_object
-                            .outbound_references
-                            .push(obj["__raw__"].as_u64()? as usize);
+                        outbound_references.push(obj["__raw__"].as_u64()? as usize);
                     } else {
                         for (_key, nested_obj) in obj.iter() {
                             if nested_obj.is_object() || nested_obj.is_array() {
@@ -146,14 +248,38 @@ fn main() {
                 }
             }
 
-            Some(return_object)
+            Some((
+                file.clone(),
+                hash,
+                ParsedObject {
+                    filename,
+                    id,
+                    outbound_references,
+                },
+            ))
+        })
+        .collect();
+
+    println!("Writing cache");
+    // `new_cache` is built purely from this run's `parsed_files`, so it never
+    // contains a stale entry for a file that's gone missing — there's nothing
+    // left to prune by the time we'd do it.
+    let mut new_cache = Cache::default();
+    let objects: Vec<Object> = parsed_files
+        .into_iter()
+        .map(|(path, hash, parsed)| {
+            new_cache.insert(path, hash, parsed.clone());
+            Object::from_parsed(parsed)
         })
         .collect();
+    new_cache
+        .to_path(&args.cache_file)
+        .expect("failed to write cache file");
 
     let mut edges = HashSet::new();
     let mut node_indices = HashMap::new();
 
-    let mut pb = ProgressBar::new(objects.len() as u64);
+    let pb = ProgressBar::new(objects.len() as u64);
     println!("Building graph");
     for mut object in objects {
         let object_id = object.id;
@@ -181,21 +307,82 @@ fn main() {
     }
     pb.finish();
 
-    // Strip out anything that doesn't have a path to 1315204
-    println!("Filtering outgoing nodes");
+    if args.detect_cycles {
+        println!("Detecting reference cycles");
+        let cycles = cycles::find_cycles(&graph);
+
+        if cycles.is_empty() {
+            println!("No reference cycles found");
+        } else {
+            for (i, cycle) in cycles.iter().enumerate() {
+                println!("Cycle {} ({} nodes):", i + 1, cycle.nodes.len());
+                for &node in &cycle.nodes {
+                    let obj = &graph[node];
+                    println!(
+                        "  {} (sno={})",
+                        obj.filename.split('/').last().unwrap(),
+                        obj.id
+                    );
+                }
+                for (from, to) in &cycle.edges {
+                    println!("  edge: {} -> {}", graph[*from].id, graph[*to].id);
+                }
+            }
+        }
+
+        return;
+    }
+
+    // Strip out anything that doesn't have a path to any target
+    println!("Resolving target nodes");
     // secret quest
     //let target_node = node_indices.get(&1315204).cloned().unwrap();
     // designer variables
     //let target_node = node_indices.get(&1040018).cloned().unwrap();
     // triune ritual
-    let target_node = node_indices
-        .get(&args.target_node_id)
-        .cloned()
-        .expect("Failed to find target node");
+    let target_nodes: Vec<NodeIndex> = args
+        .target_node_id
+        .iter()
+        .map(|id| {
+            node_indices
+                .get(id)
+                .cloned()
+                .expect("Failed to find target node")
+        })
+        .collect();
 
-    // Keep any nodes that are within a distance of 3 from the target node from the incoming direction
-    let mut keep_indices = HashSet::new();
-    let mut outgoing_edges_queue = vec![(0, target_node)];
+    if let Some(path_to_node_id) = args.path_to_node_id {
+        let source_node = target_nodes[0];
+        let destination_node = node_indices
+            .get(&path_to_node_id)
+            .cloned()
+            .expect("Failed to find path destination node");
+
+        println!("Finding shortest path");
+        let landmarks = args
+            .use_landmarks
+            .then(|| pathfind::Landmarks::build(&graph, args.landmark_count));
+
+        match pathfind::shortest_path(&graph, source_node, destination_node, landmarks.as_ref()) {
+            Some(path) => {
+                println!("Shortest path ({} hops):", path.hops());
+                for node in path.nodes {
+                    let obj = &graph[node];
+                    println!(
+                        "  {} (sno={})",
+                        obj.filename.split('/').last().unwrap(),
+                        obj.id
+                    );
+                }
+            }
+            None => println!(
+                "No path found from {} to {}",
+                args.target_node_id[0], path_to_node_id
+            ),
+        }
+
+        return;
+    }
 
     let should_ignore_node_edges = |node_id: NodeIndex, dir: Direction| {
         // - We don't care about the continent (this explodes)
@@ -208,80 +395,170 @@ fn main() {
             || graph.edges_directed(node_id, dir).count() > args.filter_count
     };
 
-    while let Some((depth, node_id)) = outgoing_edges_queue.pop() {
-        graph[node_id].distance.store(depth, Ordering::Relaxed);
-        keep_indices.insert(node_id);
+    // Run the outgoing/incoming BFS from every target independently so each
+    // one gets its own distance map, then combine the per-target keep sets
+    // per `--mode` rather than overwriting a single shared distance field.
+    let mut per_target_keep = Vec::new();
+    for (&target_id, &target_node) in args.target_node_id.iter().zip(target_nodes.iter()) {
+        println!("Filtering outgoing nodes for target {}", target_id);
+        let mut target_keep = HashSet::new();
+        let mut outgoing_edges_queue = vec![(0, target_node)];
 
-        if depth > 0 && should_ignore_node_edges(node_id, Direction::Outgoing)
-            || depth == args.outgoing_count
-        {
+        while let Some((depth, node_id)) = outgoing_edges_queue.pop() {
             graph[node_id]
-                .outgoing_filtered
-                .store(true, Ordering::Relaxed);
-            continue;
-        }
+                .distances
+                .lock()
+                .unwrap()
+                .insert(target_id, depth);
+            target_keep.insert(node_id);
+
+            if depth > 0 && should_ignore_node_edges(node_id, Direction::Outgoing)
+                || depth == args.outgoing_count
+            {
+                graph[node_id]
+                    .outgoing_filtered
+                    .store(true, Ordering::Relaxed);
+                continue;
+            }
 
-        let outgoing_edges = graph.edges_directed(node_id, Direction::Outgoing);
-        for outgoing_edge in outgoing_edges {
-            outgoing_edges_queue.push((depth + 1, outgoing_edge.target()));
+            let outgoing_edges = graph.edges_directed(node_id, Direction::Outgoing);
+            for outgoing_edge in outgoing_edges {
+                outgoing_edges_queue.push((depth + 1, outgoing_edge.target()));
+            }
         }
-    }
 
-    println!("Filtering incoming nodes");
-    // Keep any nodes that are within a distance of 3 from the target node from the incoming direction
-    let mut incoming_edges_queue = vec![(0, target_node)];
-    while let Some((depth, node_id)) = incoming_edges_queue.pop() {
-        graph[node_id].distance.store(depth, Ordering::Relaxed);
-        keep_indices.insert(node_id);
-        if depth > 0 && should_ignore_node_edges(node_id, Direction::Incoming)
-            || depth == args.incoming_count
-        {
+        println!("Filtering incoming nodes for target {}", target_id);
+        let mut incoming_edges_queue = vec![(0, target_node)];
+        while let Some((depth, node_id)) = incoming_edges_queue.pop() {
             graph[node_id]
-                .incoming_filtered
-                .store(true, Ordering::Relaxed);
-            continue;
-        }
+                .distances
+                .lock()
+                .unwrap()
+                .insert(target_id, depth);
+            target_keep.insert(node_id);
+
+            if depth > 0 && should_ignore_node_edges(node_id, Direction::Incoming)
+                || depth == args.incoming_count
+            {
+                graph[node_id]
+                    .incoming_filtered
+                    .store(true, Ordering::Relaxed);
+                continue;
+            }
 
-        let incoming_edges = graph.edges_directed(node_id, Direction::Incoming);
-        for incoming_edge in incoming_edges {
-            incoming_edges_queue.push((depth + 1, incoming_edge.source()));
+            let incoming_edges = graph.edges_directed(node_id, Direction::Incoming);
+            for incoming_edge in incoming_edges {
+                incoming_edges_queue.push((depth + 1, incoming_edge.source()));
+            }
         }
+
+        per_target_keep.push(target_keep);
     }
 
+    let keep_indices: HashSet<NodeIndex> = match args.mode {
+        NeighborhoodMode::Union => per_target_keep.iter().flatten().copied().collect(),
+        NeighborhoodMode::Intersect => {
+            let mut sets = per_target_keep.iter();
+            let first = sets.next().cloned().unwrap_or_default();
+            sets.fold(first, |acc, set| acc.intersection(set).copied().collect())
+        }
+    };
+
     println!("Removing filtered nodes from graph");
 
     graph.retain_nodes(|_g, node| keep_indices.contains(&node));
 
+    println!("Detecting cycles within neighborhood");
+    let mut neighborhood_cycles = cycles::find_cycles(&graph);
+    let mut cycle_nodes: HashSet<NodeIndex> = neighborhood_cycles
+        .iter()
+        .flat_map(|cycle| cycle.nodes.iter().copied())
+        .collect();
+    let mut cycle_edges: HashSet<(NodeIndex, NodeIndex)> = neighborhood_cycles
+        .iter()
+        .flat_map(|cycle| cycle.edges.iter().copied())
+        .collect();
+
+    if args.collapse_chains {
+        println!("Collapsing linear reference chains");
+        // `target_nodes` holds indices resolved before `retain_nodes`, which
+        // swap-removes and renumbers surviving nodes, so they're stale here.
+        // Key eligibility on SNO id instead, which survives the retain.
+        let target_ids: HashSet<usize> = args.target_node_id.iter().copied().collect();
+        let (new_graph, collapsed_count) = collapse::collapse_chains(&graph, |node| {
+            !target_ids.contains(&graph[node].id) && !cycle_nodes.contains(&node)
+        });
+        println!("Collapsed {} chain(s)", collapsed_count);
+        graph = new_graph;
+
+        // Node indices were rebuilt, so cycle highlighting has to be
+        // recomputed against the new graph rather than remapped.
+        neighborhood_cycles = cycles::find_cycles(&graph);
+        cycle_nodes = neighborhood_cycles
+            .iter()
+            .flat_map(|cycle| cycle.nodes.iter().copied())
+            .collect();
+        cycle_edges = neighborhood_cycles
+            .iter()
+            .flat_map(|cycle| cycle.edges.iter().copied())
+            .collect();
+    }
+
     println!("Writing graph");
 
-    let binding = |graph: &Graph<_, _>, node_ref: (NodeIndex, &Object)| {
-        if node_ref.1.id == args.target_node_id {
-            ["fillcolor=blue", "style=filled"].join(" ")
-        } else {
-            "".to_string()
-        }
+    // Give each target its own color; nodes reached by more than one target
+    // (a shared dependency) are shaded darker the more targets reach them.
+    const TARGET_PALETTE: &[&str] = &[
+        "deepskyblue",
+        "gold",
+        "mediumseagreen",
+        "orchid",
+        "tomato",
+        "turquoise",
+        "khaki",
+        "salmon",
+    ];
+    let total_targets = args.target_node_id.len();
+    let mut node_styles = HashMap::new();
+    for node in graph.node_indices() {
+        let obj = &graph[node];
+        let reached_by: Vec<usize> = args
+            .target_node_id
+            .iter()
+            .enumerate()
+            .filter(|(_, target_id)| obj.distances.lock().unwrap().contains_key(target_id))
+            .map(|(i, _)| i)
+            .collect();
+
+        let style = match reached_by.len() {
+            0 => String::new(),
+            1 => format!(
+                "fillcolor={} style=filled",
+                TARGET_PALETTE[reached_by[0] % TARGET_PALETTE.len()]
+            ),
+            shared => {
+                let intensity = shared as f64 / total_targets.max(1) as f64;
+                let value = 1.0 - intensity * 0.6;
+                format!("fillcolor=\"0.0 0.0 {:.2}\" style=filled", value)
+            }
+        };
+        node_styles.insert(node, style);
+    }
+    for &node in &cycle_nodes {
+        node_styles.insert(node, "fillcolor=darkred style=filled".to_string());
+    }
+
+    let exporter: Box<dyn Exporter> = match args.format {
+        Format::Dot => Box::new(DotExporter {
+            node_styles,
+            cycle_edges,
+        }),
+        Format::Graphml => Box::new(GraphmlExporter),
+        Format::Json => Box::new(JsonExporter),
     };
-    let dot_data = Dot::with_attr_getters(
-        &graph,
-        &[Config::EdgeNoLabel],
-        &|graph, edge_ref| ["color=white"].concat(),
-        &binding,
-    );
-    let header = r#"digraph {
-        bgcolor=black
-        node [color=white fillcolor=black style=filled fontcolor=white shape=box]
-        edge [color=white]
-    "#;
-    let digraph_str = "digraph {";
-    let mut generated_dot_file = Vec::new();
-    write!(generated_dot_file, "{}", dot_data).expect("failed to generate dot output");
 
     let mut out_file = std::fs::File::create(&args.out_file).expect("failed to create output file");
-    out_file
-        .write_all(header.as_bytes())
-        .expect("failed to write all bytes for dot header");
-
-    out_file
-        .write_all(&generated_dot_file[digraph_str.len()..])
-        .expect("failed to write generated dot data");
+    exporter
+        .export(&graph, &mut out_file)
+        .expect("failed to write exported graph");
 }
@@ -0,0 +1,157 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use petgraph::{algo::dijkstra, prelude::*};
+
+use crate::Object;
+
+/// A single entry in the A*/Dijkstra open set, ordered so `BinaryHeap` (a
+/// max-heap) pops the lowest `estimate` first.
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct HeapEntry {
+    cost: usize,
+    estimate: usize,
+    node: NodeIndex,
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Precomputed distances from a handful of high-degree "landmark" nodes to
+/// every other node, used as an admissible A* heuristic lower bound. The
+/// graph is directed, so only `d(L, target) - d(L, n)` bounds the remaining
+/// distance `d(n, target)`; the reverse difference bounds `d(target, n)`
+/// instead and would make the heuristic inadmissible if folded in via
+/// `abs_diff`: `h(n) = max_L max(0, d(L, target) - d(L, n))`.
+pub struct Landmarks {
+    distances: Vec<HashMap<NodeIndex, usize>>,
+}
+
+impl Landmarks {
+    /// Picks the `count` highest-degree nodes in `graph` as landmarks and
+    /// precomputes their distance to every reachable node with Dijkstra.
+    pub fn build(graph: &DiGraph<Object, i32>, count: usize) -> Self {
+        let mut by_degree: Vec<NodeIndex> = graph.node_indices().collect();
+        by_degree.sort_by_key(|&node| {
+            std::cmp::Reverse(
+                graph.edges_directed(node, Direction::Outgoing).count()
+                    + graph.edges_directed(node, Direction::Incoming).count(),
+            )
+        });
+        by_degree.truncate(count);
+
+        let distances = by_degree
+            .into_iter()
+            .map(|landmark| dijkstra(graph, landmark, None, |_| 1usize))
+            .collect();
+
+        Landmarks { distances }
+    }
+
+    fn estimate(&self, node: NodeIndex, target: NodeIndex) -> usize {
+        self.distances
+            .iter()
+            .filter_map(|landmark_distances| {
+                let to_target = *landmark_distances.get(&target)?;
+                let to_node = *landmark_distances.get(&node)?;
+                Some(to_target.saturating_sub(to_node))
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// An ordered chain of nodes from a path query's source to its target,
+/// inclusive of both endpoints.
+pub struct Path {
+    pub nodes: Vec<NodeIndex>,
+}
+
+impl Path {
+    /// Number of edges traversed by the path.
+    pub fn hops(&self) -> usize {
+        self.nodes.len().saturating_sub(1)
+    }
+}
+
+/// Finds the single shortest reference path from `source` to `target`.
+/// Passing `landmarks` runs an A*-guided search; passing `None` degrades to
+/// plain Dijkstra (a heuristic that's always 0). Does not enumerate the k
+/// shortest paths or their induced subgraph — callers needing that get only
+/// this one path.
+pub fn shortest_path(
+    graph: &DiGraph<Object, i32>,
+    source: NodeIndex,
+    target: NodeIndex,
+    landmarks: Option<&Landmarks>,
+) -> Option<Path> {
+    let heuristic = |node: NodeIndex| match landmarks {
+        Some(landmarks) => landmarks.estimate(node, target),
+        None => 0,
+    };
+
+    let mut best_cost = HashMap::new();
+    let mut predecessor = HashMap::new();
+    let mut open = BinaryHeap::new();
+
+    best_cost.insert(source, 0usize);
+    open.push(HeapEntry {
+        cost: 0,
+        estimate: heuristic(source),
+        node: source,
+    });
+
+    while let Some(HeapEntry { cost, node, .. }) = open.pop() {
+        if node == target {
+            return Some(Path {
+                nodes: reconstruct_path(&predecessor, source, target),
+            });
+        }
+
+        if cost > best_cost.get(&node).copied().unwrap_or(usize::MAX) {
+            continue;
+        }
+
+        for edge in graph.edges_directed(node, Direction::Outgoing) {
+            let next = edge.target();
+            let next_cost = cost + 1;
+            if next_cost < best_cost.get(&next).copied().unwrap_or(usize::MAX) {
+                best_cost.insert(next, next_cost);
+                predecessor.insert(next, node);
+                open.push(HeapEntry {
+                    cost: next_cost,
+                    estimate: next_cost + heuristic(next),
+                    node: next,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+fn reconstruct_path(
+    predecessor: &HashMap<NodeIndex, NodeIndex>,
+    source: NodeIndex,
+    target: NodeIndex,
+) -> Vec<NodeIndex> {
+    let mut path = vec![target];
+    let mut current = target;
+    while current != source {
+        current = predecessor[&current];
+        path.push(current);
+    }
+    path.reverse();
+    path
+}